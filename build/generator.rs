@@ -2,15 +2,27 @@ use std::ffi::OsStr;
 use std::fs::{File, OpenOptions};
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command};
-use std::sync::Arc;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use std::{env, fs, io, thread};
+use std::{env, fs, thread};
 
 use crate::docs::transfer_bindings_to_docs;
+use crate::prebuilt;
 
+use self::error::{ModuleGenError, ModuleGenErrors};
+use self::gen_cache::{GenCache, ModuleFingerprint};
+use self::unique_entries::UniqueEntries;
 use super::{files_with_extension, files_with_predicate, Library, Result, HOST_TRIPLE, MODULES, OUT_DIR, SRC_CPP_DIR, SRC_DIR};
 
+mod error;
+mod gen_cache;
+mod unique_entries;
+
+/// When set, a module whose `binding-generator` subprocess fails is dropped from the module list instead
+/// of failing the whole build, letting a partial but compilable hub be produced for debugging.
+const CONTINUE_ON_MODULE_ERROR_ENV: &str = "OCVRS_CONTINUE_ON_MODULE_ERROR";
+
 fn is_type_file(path: &Path, module: &str) -> bool {
 	path.file_stem().and_then(OsStr::to_str).map_or(false, |stem| {
 		let mut stem_chars = stem.chars();
@@ -38,7 +50,7 @@ fn run_binding_generator(
 	job_server: jobserver::Client,
 	opencv_header_dir: &Path,
 	opencv: &Library,
-) -> Result<()> {
+) -> Result<Vec<String>> {
 	let additional_include_dirs = opencv
 		.include_paths
 		.iter()
@@ -49,7 +61,8 @@ fn run_binding_generator(
 	let clang = clang::Clang::new().expect("Cannot initialize clang");
 	eprintln!("=== Clang: {}", clang::get_version());
 	let gen = opencv_binding_generator::Generator::new(opencv_header_dir, &additional_include_dirs, &SRC_CPP_DIR, clang);
-	eprintln!("=== Clang command line args: {:#?}", gen.build_clang_command_line_args());
+	let clang_command_line_args = gen.build_clang_command_line_args();
+	eprintln!("=== Clang command line args: {clang_command_line_args:#?}");
 
 	eprintln!("=== Building binding-generator binary:");
 	if let Some(child_stderr) = generator_build.stderr.take() {
@@ -75,50 +88,121 @@ fn run_binding_generator(
 		}
 	};
 
-	let additional_include_dirs = Arc::new(
-		additional_include_dirs
-			.iter()
-			.cloned()
-			.map(|p| {
-				p.to_str()
-					.expect("Can't convert additional include dir to UTF-8 string")
-					.to_string()
-			})
-			.collect::<Vec<_>>(),
-	);
+	let additional_include_dirs_str = additional_include_dirs
+		.iter()
+		.cloned()
+		.map(|p| {
+			p.to_str()
+				.expect("Can't convert additional include dir to UTF-8 string")
+				.to_string()
+		})
+		.collect::<Vec<_>>();
+	let additional_include_dirs = Arc::new(additional_include_dirs_str.clone());
 	let opencv_header_dir = Arc::new(opencv_header_dir.to_owned());
-	let mut join_handles = Vec::with_capacity(modules.len());
+
+	// Figure out up front which modules can be skipped because their fingerprint didn't change since the
+	// last run, restoring their cached artifacts right away so only the rest needs a worker thread.
+	let cache = Arc::new(Mutex::new(GenCache::open(&OUT_DIR)));
+	let mut modules_to_generate = Vec::with_capacity(modules.len());
+	for module in modules {
+		let fingerprint = ModuleFingerprint::compute(
+			module,
+			&opencv_header_dir,
+			&SRC_CPP_DIR,
+			&clang_command_line_args,
+			&additional_include_dirs_str,
+			&binding_gen,
+		)?;
+		let is_fresh = cache.lock().expect("Cache mutex poisoned").is_fresh(module, &fingerprint);
+		if is_fresh {
+			eprintln!("=== Reusing cached bindings for: {module}");
+			cache.lock().expect("Cache mutex poisoned").restore(module, &OUT_DIR)?;
+		} else {
+			modules_to_generate.push((module, fingerprint));
+		}
+	}
+
+	let mut join_handles = Vec::with_capacity(modules_to_generate.len());
 	let start = Instant::now();
-	modules.iter().for_each(|module| {
+	// We never spawn more threads than there are modules to generate, so acquiring one token per module
+	// here can't outrun the job server and deadlock it.
+	modules_to_generate.into_iter().for_each(|(module, fingerprint)| {
 		let binding_gen = binding_gen.clone();
+		let cache = Arc::clone(&cache);
+		let job_server = job_server.clone();
 		let token = job_server.acquire().expect("Can't acquire token from job server");
 		let join_handle = thread::spawn({
 			let additional_include_dirs = Arc::clone(&additional_include_dirs);
 			let opencv_header_dir = Arc::clone(&opencv_header_dir);
-			move || {
+			move || -> std::result::Result<(), ModuleGenError> {
+				// Binds (moves) `token` into this closure's scope so it stays alive - and the job server
+				// slot it represents stays taken - until the subprocess below actually finishes, on every
+				// return path including the error ones.
+				let _token = token;
 				let mut bin_generator = Command::new(binding_gen);
 				bin_generator
 					.arg(&*opencv_header_dir)
 					.arg(&*SRC_CPP_DIR)
 					.arg(&*OUT_DIR)
 					.arg(module)
-					.arg(additional_include_dirs.join(","));
+					.arg(additional_include_dirs.join(","))
+					.stderr(Stdio::piped());
+				// Let the binding generator inherit the job server so it can acquire extra tokens of its own
+				// to render classes/functions of a single module concurrently, on top of the token this
+				// thread took for it above. We keep our token until the subprocess is done (it's dropped
+				// along with the rest of this closure's locals), the same way the parent bounded concurrent
+				// `binding-generator` processes to roughly `-j` before this change — letting it go earlier
+				// would let every module's subprocess race to parse the full OpenCV header set at once.
+				job_server.configure(&mut bin_generator);
 				eprintln!("=== Running: {bin_generator:?}");
-				let res = bin_generator.status().expect("Can't run bindings generator");
-				if !res.success() {
-					panic!("Failed to run the bindings generator");
+				let mut child = bin_generator
+					.spawn()
+					.map_err(|err| ModuleGenError::new(module.clone(), None, err.to_string()))?;
+				let mut captured_stderr = String::new();
+				if let Some(child_stderr) = child.stderr.take() {
+					for line in BufReader::new(child_stderr).lines().flatten() {
+						eprintln!("=== {line}");
+						captured_stderr.push_str(&line);
+						captured_stderr.push('\n');
+					}
+				}
+				let status = child
+					.wait()
+					.map_err(|err| ModuleGenError::new(module.clone(), None, err.to_string()))?;
+				if !status.success() {
+					return Err(ModuleGenError::new(module.clone(), Some(status), captured_stderr));
 				}
 				eprintln!("=== Generated: {module}");
-				drop(token); // needed to move the token to the thread
+				cache
+					.lock()
+					.expect("Cache mutex poisoned")
+					.store(module, &OUT_DIR, fingerprint)
+					.map_err(|err| ModuleGenError::new(module.clone(), None, err.to_string()))?;
+				Ok(())
 			}
 		});
-		join_handles.push(join_handle);
+		join_handles.push((module, join_handle));
 	});
-	for join_handle in join_handles {
-		join_handle.join().expect("Generator thread panicked");
+	let mut errors = Vec::new();
+	let mut failed_modules = std::collections::HashSet::new();
+	for (module, join_handle) in join_handles {
+		if let Err(err) = join_handle.join().expect("Generator thread panicked") {
+			failed_modules.insert(module.as_str());
+			errors.push(err);
+		}
 	}
+	cache.lock().expect("Cache mutex poisoned").save().expect("Can't save the generator cache manifest");
 	eprintln!("=== Total binding generation time: {:?}", start.elapsed());
-	Ok(())
+	if !errors.is_empty() {
+		let errors = ModuleGenErrors(errors);
+		if env::var_os(CONTINUE_ON_MODULE_ERROR_ENV).is_some() {
+			eprintln!("=== {errors}");
+			eprintln!("=== {CONTINUE_ON_MODULE_ERROR_ENV} is set, continuing without the failed module(s)");
+		} else {
+			return Err(errors.into());
+		}
+	}
+	Ok(modules.iter().filter(|module| !failed_modules.contains(module.as_str())).cloned().collect())
 }
 
 fn collect_generated_bindings(modules: &[String], target_module_dir: &Path, manual_dir: &Path) -> Result<()> {
@@ -163,7 +247,8 @@ fn collect_generated_bindings(modules: &[String], target_module_dir: &Path, manu
 	writeln!(sys_rs)?;
 
 	for module in modules {
-		// merge multiple *-type.cpp files into a single module_types.hpp
+		// merge multiple *-type.cpp files into a single module_types.hpp, deduplicating entries that
+		// several translation units produced identically
 		let module_cpp = OUT_DIR.join(format!("{module}.cpp"));
 		if module_cpp.is_file() {
 			let module_types_cpp = OUT_DIR.join(format!("{module}_types.hpp"));
@@ -178,8 +263,12 @@ fn collect_generated_bindings(modules: &[String], target_module_dir: &Path, manu
 				.filter(|f| is_type_file(f, module))
 				.collect::<Vec<_>>();
 			type_files.sort_unstable();
+			let mut unique_types = UniqueEntries::new();
+			for entry in &type_files {
+				unique_types.add_file(entry)?;
+			}
+			unique_types.write_indented(&mut module_types_file, "")?;
 			for entry in type_files {
-				io::copy(&mut BufReader::new(File::open(&entry)?), &mut module_types_file)?;
 				let _ = fs::remove_file(entry);
 			}
 		}
@@ -199,39 +288,42 @@ fn collect_generated_bindings(modules: &[String], target_module_dir: &Path, manu
 		writeln!(module_rs, "}}")?;
 		let _ = fs::remove_file(module_src_file);
 
-		// merge multiple *-.type.rs files into a single types.rs
-		let mut header_written = false;
+		// merge multiple *-.type.rs files into a single types.rs, deduplicating identical entries and
+		// keeping the merged output name-sorted so it's reproducible regardless of filename order
 		let mut type_files = files_with_extension(&OUT_DIR, "rs")?
 			.filter(|f| is_type_file(f, module))
 			.collect::<Vec<_>>();
 		type_files.sort_unstable();
-		for entry in type_files {
+		let mut unique_types = UniqueEntries::new();
+		for entry in &type_files {
 			if entry.metadata().map(|meta| meta.len()).unwrap_or(0) > 0 {
-				if !header_written {
-					write_has_module(&mut types_rs, module)?;
-					writeln!(types_rs, "mod {module}_types {{")?;
-					writeln!(types_rs, "\tuse crate::{{mod_prelude::*, core, types, sys}};")?;
-					writeln!(types_rs)?;
-					header_written = true;
-				}
-				copy_indent(BufReader::new(File::open(&entry)?), &mut types_rs, "\t")?;
+				unique_types.add_file(entry)?;
 			}
-			let _ = fs::remove_file(entry);
 		}
-		if header_written {
+		if !unique_types.is_empty() {
+			write_has_module(&mut types_rs, module)?;
+			writeln!(types_rs, "mod {module}_types {{")?;
+			writeln!(types_rs, "\tuse crate::{{mod_prelude::*, core, types, sys}};")?;
+			writeln!(types_rs)?;
+			unique_types.write_indented(&mut types_rs, "\t")?;
 			writeln!(types_rs, "}}")?;
 			write_has_module(&mut types_rs, module)?;
 			writeln!(types_rs, "pub use {module}_types::*;")?;
 			writeln!(types_rs)?;
 		}
+		for entry in type_files {
+			let _ = fs::remove_file(entry);
+		}
 
-		// merge module-specific *.externs.rs into a single sys.rs
+		// merge module-specific *.externs.rs into a single sys.rs, deduplicating entries the same way
 		let externs_rs = OUT_DIR.join(format!("{module}.externs.rs"));
 		write_has_module(&mut sys_rs, module)?;
 		writeln!(sys_rs, "mod {module}_sys {{")?;
 		writeln!(sys_rs, "\tuse super::*;")?;
 		writeln!(sys_rs)?;
-		copy_indent(BufReader::new(File::open(&externs_rs)?), &mut sys_rs, "\t")?;
+		let mut unique_externs = UniqueEntries::new();
+		unique_externs.add_file(&externs_rs)?;
+		unique_externs.write_indented(&mut sys_rs, "\t")?;
 		let _ = fs::remove_file(externs_rs);
 		writeln!(sys_rs, "}}")?;
 		write_has_module(&mut sys_rs, module)?;
@@ -266,7 +358,7 @@ pub fn gen_wrapper(
 	opencv_header_dir: &Path,
 	opencv: &Library,
 	job_server: jobserver::Client,
-	generator_build: Child,
+	mut generator_build: Child,
 ) -> Result<()> {
 	let target_docs_dir = env::var_os("OCVRS_DOCS_GENERATE_DIR").map(PathBuf::from);
 	let target_module_dir = OUT_DIR.join("opencv");
@@ -282,8 +374,10 @@ pub fn gen_wrapper(
 	}
 	eprintln!("=== Using OpenCV headers from: {}", opencv_header_dir.display());
 
+	// Keep the generator cache's manifest and cache directory around, they must survive this wipe to be
+	// of any use to `GenCache::open` on the next invocation.
 	let non_dll_files = files_with_predicate(&OUT_DIR, |p| {
-		p.extension().map_or(true, |ext| !ext.eq_ignore_ascii_case("dll"))
+		p.extension().map_or(true, |ext| !ext.eq_ignore_ascii_case("dll")) && !gen_cache::is_cache_path(&OUT_DIR, p)
 	})?;
 	for path in non_dll_files {
 		let _ = fs::remove_file(path);
@@ -291,9 +385,30 @@ pub fn gen_wrapper(
 
 	let modules = MODULES.get().expect("MODULES not initialized");
 
-	run_binding_generator(modules, generator_build, job_server, opencv_header_dir, opencv)?;
+	// A sandbox without a clang toolchain (docs.rs-like environments, some cross builds) can't run the
+	// binding generator at all, so probe libclang upfront and fall back to the checked-in prebuilt
+	// bindings the same way an explicit `OCVRS_USE_PREBUILT_BINDINGS=1` would.
+	let libclang_available = clang::Clang::new().is_ok();
+	let prebuilt_dir = prebuilt::prebuilt_bindings_dir(&SRC_DIR, opencv);
+	if prebuilt::use_prebuilt_bindings() || !libclang_available {
+		if !libclang_available {
+			eprintln!("=== Clang is not available, falling back to prebuilt bindings");
+		}
+		eprintln!("=== Using prebuilt bindings from: {}", prebuilt_dir.display());
+		// We're not going to run it, no need to let it keep building in the background.
+		let _ = generator_build.kill();
+		let _ = generator_build.wait();
+		prebuilt::collect_prebuilt_bindings(modules, &prebuilt_dir, &target_module_dir, &OUT_DIR)?;
+	} else {
+		// May be a subset of `modules` if OCVRS_CONTINUE_ON_MODULE_ERROR dropped the ones that failed.
+		let generated_modules = run_binding_generator(modules, generator_build, job_server, opencv_header_dir, opencv)?;
+
+		collect_generated_bindings(&generated_modules, &target_module_dir, &manual_dir)?;
 
-	collect_generated_bindings(modules, &target_module_dir, &manual_dir)?;
+		if prebuilt::export_requested() {
+			prebuilt::export_prebuilt_bindings(&OUT_DIR, &target_module_dir, &generated_modules, &prebuilt_dir)?;
+		}
+	}
 
 	if let Some(target_docs_dir) = target_docs_dir {
 		if !target_docs_dir.exists() {
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::process::ExitStatus;
+
+const STDERR_TAIL_LINES: usize = 20;
+
+/// A single module's `binding-generator` subprocess failing to start or exiting unsuccessfully. Carries
+/// enough context (which module, its exit status and a tail of its stderr) to diagnose the failure without
+/// needing to single out the module and re-run it in isolation.
+pub struct ModuleGenError {
+	pub module: String,
+	status: Option<ExitStatus>,
+	stderr: String,
+}
+
+impl ModuleGenError {
+	pub fn new(module: impl Into<String>, status: Option<ExitStatus>, stderr: impl Into<String>) -> Self {
+		Self {
+			module: module.into(),
+			status,
+			stderr: stderr.into(),
+		}
+	}
+}
+
+impl fmt::Display for ModuleGenError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.status {
+			Some(status) => write!(f, "module `{}` failed to generate ({status})", self.module)?,
+			None => write!(f, "module `{}` failed to start the binding generator", self.module)?,
+		}
+		let tail = self.stderr.lines().rev().take(STDERR_TAIL_LINES).collect::<Vec<_>>();
+		if !tail.is_empty() {
+			write!(f, ", stderr tail:")?;
+			for line in tail.into_iter().rev() {
+				write!(f, "\n    {line}")?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Debug for ModuleGenError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+
+impl std::error::Error for ModuleGenError {}
+
+/// Aggregates the [ModuleGenError]s of every module that failed to generate in a single build, so a build
+/// failure lists all of them instead of stopping at the first panicking thread.
+pub struct ModuleGenErrors(pub Vec<ModuleGenError>);
+
+impl fmt::Display for ModuleGenErrors {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{} module(s) failed to generate bindings:", self.0.len())?;
+		for (i, err) in self.0.iter().enumerate() {
+			if i > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "- {err}")?;
+		}
+		Ok(())
+	}
+}
+
+impl fmt::Debug for ModuleGenErrors {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+
+impl std::error::Error for ModuleGenErrors {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn display_without_status_mentions_failure_to_start() {
+		let err = ModuleGenError::new("core", None, "");
+		assert_eq!(err.to_string(), "module `core` failed to start the binding generator");
+	}
+
+	#[test]
+	fn display_includes_stderr_tail() {
+		let err = ModuleGenError::new("core", None, "first line\nsecond line\n");
+		assert_eq!(
+			err.to_string(),
+			"module `core` failed to start the binding generator, stderr tail:\n    first line\n    second line"
+		);
+	}
+
+	#[test]
+	fn display_truncates_stderr_tail_to_last_lines() {
+		let stderr = (0..STDERR_TAIL_LINES + 5)
+			.map(|i| format!("line {i}"))
+			.collect::<Vec<_>>()
+			.join("\n");
+		let err = ModuleGenError::new("core", None, stderr);
+		let displayed = err.to_string();
+		let tail_lines = displayed.lines().skip(1).count();
+		assert_eq!(tail_lines, STDERR_TAIL_LINES);
+		assert!(displayed.contains("line 5"));
+		assert!(!displayed.contains("line 4\n"));
+	}
+
+	#[test]
+	fn display_aggregates_multiple_errors_in_order() {
+		let errors = ModuleGenErrors(vec![
+			ModuleGenError::new("core", None, ""),
+			ModuleGenError::new("imgproc", None, ""),
+		]);
+		assert_eq!(
+			errors.to_string(),
+			"2 module(s) failed to generate bindings:\n\
+			 - module `core` failed to start the binding generator\n\
+			 - module `imgproc` failed to start the binding generator"
+		);
+	}
+}
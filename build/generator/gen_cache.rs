@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::super::{files_with_extension, HOST_TRIPLE};
+use super::is_type_file;
+
+/// Bump this whenever the shape of the manifest file or the set of inputs that go into
+/// [ModuleFingerprint::compute] changes in a way that makes previously cached fingerprints unsafe to trust.
+const MANIFEST_SCHEMA_VERSION: u32 = 2;
+const MANIFEST_FILE_NAME: &str = ".ocvrs-gen-manifest.json";
+const CACHE_DIR_NAME: &str = ".ocvrs-gen-cache";
+
+/// Hash of everything that influences the generated output of a single module: the OpenCV header(s) it
+/// includes, the contents of `SRC_CPP_DIR` (the manual/common wrapper headers passed to every
+/// `binding-generator` invocation), the clang command line used to parse them, the additional include
+/// directories and the `binding-generator` binary that will be doing the parsing.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ModuleFingerprint(String);
+
+impl ModuleFingerprint {
+	pub fn compute(
+		module: &str,
+		opencv_header_dir: &Path,
+		src_cpp_dir: &Path,
+		clang_command_line_args: &[String],
+		additional_include_dirs: &[String],
+		binding_gen_bin: &Path,
+	) -> io::Result<Self> {
+		let mut hasher = std::collections::hash_map::DefaultHasher::new();
+		module.hash(&mut hasher);
+		hash_module_headers(&mut hasher, opencv_header_dir, module)?;
+		hash_dir_contents(&mut hasher, src_cpp_dir)?;
+		clang_command_line_args.hash(&mut hasher);
+		additional_include_dirs.hash(&mut hasher);
+		let bin_meta = fs::metadata(binding_gen_bin)?;
+		bin_meta.len().hash(&mut hasher);
+		if let Ok(modified) = bin_meta.modified() {
+			modified.hash(&mut hasher);
+		}
+		Ok(Self(format!("{:016x}", hasher.finish())))
+	}
+}
+
+/// Hashes the content of the headers `module` includes: its single entry header (`opencv2/{module}.hpp`)
+/// plus every header under its detail directory (`opencv2/{module}/`), in path-sorted order so the result
+/// doesn't depend on directory listing order. Hashing the directory path alone (as an earlier version of
+/// this function did) can't tell a header edit from a no-op rebuild, so an in-place OpenCV header upgrade
+/// (same include path, new content - the common case for a system package upgrade or a local OpenCV
+/// rebuild) would never bust the fingerprint and a module would keep reusing stale cached bindings forever.
+fn hash_module_headers(hasher: &mut impl Hasher, opencv_header_dir: &Path, module: &str) -> io::Result<()> {
+	let mut header_paths = Vec::new();
+	let entry_header = opencv_header_dir.join("opencv2").join(format!("{module}.hpp"));
+	if entry_header.is_file() {
+		header_paths.push(entry_header);
+	}
+	header_paths.sort_unstable();
+	for header_path in header_paths {
+		header_path.hash(hasher);
+		fs::read(&header_path)?.hash(hasher);
+	}
+	hash_dir_contents(hasher, &opencv_header_dir.join("opencv2").join(module))
+}
+
+/// Hashes the content of every file under `dir`, in path-sorted order so the result doesn't depend on
+/// directory listing order. Does nothing if `dir` doesn't exist. Shared by [hash_module_headers] (for a
+/// module's header detail directory) and by [ModuleFingerprint::compute] directly (for `SRC_CPP_DIR`,
+/// whose manual/common wrapper headers are shared across every module and are a direct input to what
+/// `binding-generator` parses, same as the OpenCV headers themselves).
+fn hash_dir_contents(hasher: &mut impl Hasher, dir: &Path) -> io::Result<()> {
+	let mut paths = Vec::new();
+	if dir.is_dir() {
+		collect_files_recursively(dir, &mut paths)?;
+	}
+	paths.sort_unstable();
+	for path in paths {
+		path.hash(hasher);
+		fs::read(&path)?.hash(hasher);
+	}
+	Ok(())
+}
+
+fn collect_files_recursively(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			collect_files_recursively(&path, out)?;
+		} else {
+			out.push(path);
+		}
+	}
+	Ok(())
+}
+
+/// Persistent, per-module cache of generated bindings keyed by [ModuleFingerprint]. Backs the incremental
+/// regeneration in `run_binding_generator`: a module whose fingerprint didn't change since the last run can
+/// have its `{module}.cpp`/`{module}.rs`/`{module}.externs.rs`/`*.type.*` artifacts restored straight from
+/// `cache_dir` instead of re-invoking the `binding-generator` binary.
+pub struct GenCache {
+	cache_dir: PathBuf,
+	manifest_path: PathBuf,
+	entries: HashMap<String, ModuleFingerprint>,
+}
+
+/// Returns `true` if `path` is the manifest file or lives inside the cache directory that [GenCache::open]
+/// keeps under `out_dir`. `gen_wrapper`'s "wipe everything non-DLL in `OUT_DIR`" pass must exempt these, or
+/// it would destroy the cache before `GenCache::open` ever gets a chance to read it back.
+pub fn is_cache_path(out_dir: &Path, path: &Path) -> bool {
+	path == out_dir.join(MANIFEST_FILE_NAME) || path.starts_with(out_dir.join(CACHE_DIR_NAME))
+}
+
+impl GenCache {
+	/// Opens (and, if necessary, invalidates) the cache located under `out_dir`. A manifest written by a
+	/// different schema version or produced on a different host triple invalidates the cache: the whole
+	/// cache directory is wiped so that every module is unconditionally regenerated. A missing or
+	/// unreadable manifest opens the cache with no entries, which regenerates every module too, but leaves
+	/// the files in the cache directory in place.
+	pub fn open(out_dir: &Path) -> Self {
+		let cache_dir = out_dir.join(CACHE_DIR_NAME);
+		let manifest_path = out_dir.join(MANIFEST_FILE_NAME);
+		let loaded = fs::read_to_string(&manifest_path).ok().and_then(|contents| parse_manifest(&contents));
+		let entries = match loaded {
+			Some((schema_version, host_triple, entries))
+				if schema_version == MANIFEST_SCHEMA_VERSION && host_triple.as_deref() == HOST_TRIPLE.as_deref() =>
+			{
+				entries
+			}
+			Some(_) => {
+				eprintln!("=== Binding generator cache manifest is stale, invalidating the cache");
+				let _ = fs::remove_dir_all(&cache_dir);
+				HashMap::new()
+			}
+			None => HashMap::new(),
+		};
+		let _ = fs::create_dir_all(&cache_dir);
+		Self {
+			cache_dir,
+			manifest_path,
+			entries,
+		}
+	}
+
+	/// Returns `true` if `module` has a cached entry matching `fingerprint` and the cached artifacts are
+	/// still present on disk.
+	pub fn is_fresh(&self, module: &str, fingerprint: &ModuleFingerprint) -> bool {
+		self.entries.get(module) == Some(fingerprint) && self.cache_dir.join(module).is_dir()
+	}
+
+	/// Copies the cached artifacts for `module` back into `out_dir`, overwriting the equivalent files that
+	/// `run_binding_generator`/`collect_generated_bindings` expect to find there.
+	pub fn restore(&self, module: &str, out_dir: &Path) -> io::Result<()> {
+		let module_cache_dir = self.cache_dir.join(module);
+		for entry in fs::read_dir(&module_cache_dir)? {
+			let entry = entry?;
+			let target = out_dir.join(entry.file_name());
+			fs::copy(entry.path(), target)?;
+		}
+		Ok(())
+	}
+
+	/// Copies the freshly generated artifacts for `module` out of `out_dir` into the cache and records
+	/// `fingerprint` as the one they correspond to.
+	pub fn store(&mut self, module: &str, out_dir: &Path, fingerprint: ModuleFingerprint) -> io::Result<()> {
+		let module_cache_dir = self.cache_dir.join(module);
+		let _ = fs::remove_dir_all(&module_cache_dir);
+		fs::create_dir_all(&module_cache_dir)?;
+		for ext in ["cpp", "rs"] {
+			for path in files_with_extension(out_dir, ext)? {
+				let is_module_file = path.file_stem().and_then(|s| s.to_str()) == Some(module);
+				if is_module_file || is_type_file(&path, module) {
+					fs::copy(&path, module_cache_dir.join(path.file_name().expect("File without a name"))).map(drop)?;
+				}
+			}
+		}
+		let externs_rs = out_dir.join(format!("{module}.externs.rs"));
+		if externs_rs.is_file() {
+			fs::copy(&externs_rs, module_cache_dir.join(format!("{module}.externs.rs")))?;
+		}
+		self.entries.insert(module.to_string(), fingerprint);
+		Ok(())
+	}
+
+	/// Persists the manifest to disk, must be called after the last `store()` call of a build.
+	pub fn save(&self) -> io::Result<()> {
+		fs::write(&self.manifest_path, serialize_manifest(&self.entries))
+	}
+}
+
+fn serialize_manifest(entries: &HashMap<String, ModuleFingerprint>) -> String {
+	let mut out = String::new();
+	writeln!(out, "{{").unwrap();
+	writeln!(out, "\t\"schema_version\": {MANIFEST_SCHEMA_VERSION},").unwrap();
+	writeln!(out, "\t\"host_triple\": {:?},", HOST_TRIPLE.as_deref().unwrap_or("")).unwrap();
+	writeln!(out, "\t\"modules\": {{").unwrap();
+	let mut modules = entries.iter().collect::<Vec<_>>();
+	modules.sort_unstable_by_key(|(module, _)| module.as_str());
+	for (i, (module, fingerprint)) in modules.iter().enumerate() {
+		let comma = if i + 1 == modules.len() { "" } else { "," };
+		writeln!(out, "\t\t{module:?}: {:?}{comma}", fingerprint.0).unwrap();
+	}
+	writeln!(out, "\t}}").unwrap();
+	writeln!(out, "}}").unwrap();
+	out
+}
+
+/// Hand-rolled parser for the fixed shape written by [serialize_manifest], avoids pulling in a JSON crate
+/// just for this build-script-internal manifest.
+fn parse_manifest(contents: &str) -> Option<(u32, Option<String>, HashMap<String, ModuleFingerprint>)> {
+	let mut schema_version = None;
+	let mut host_triple = None;
+	let mut modules = HashMap::new();
+	let mut in_modules = false;
+	for line in contents.lines() {
+		let line = line.trim().trim_end_matches(',');
+		if let Some(rest) = line.strip_prefix("\"schema_version\":") {
+			schema_version = rest.trim().parse().ok();
+		} else if let Some(rest) = line.strip_prefix("\"host_triple\":") {
+			host_triple = Some(rest.trim().trim_matches('"').to_string()).filter(|triple| !triple.is_empty());
+		} else if line.starts_with("\"modules\"") {
+			in_modules = true;
+		} else if in_modules {
+			if let Some((key, value)) = line.split_once(':') {
+				let key = key.trim().trim_matches('"');
+				let value = value.trim().trim_matches('"');
+				if !key.is_empty() && !value.is_empty() {
+					modules.insert(key.to_string(), ModuleFingerprint(value.to_string()));
+				}
+			}
+		}
+	}
+	Some((schema_version?, host_triple, modules))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn manifest_round_trips_through_serialize_and_parse() {
+		let mut entries = HashMap::new();
+		entries.insert("core".to_string(), ModuleFingerprint("aaaa1111aaaa1111".to_string()));
+		entries.insert("imgproc".to_string(), ModuleFingerprint("bbbb2222bbbb2222".to_string()));
+
+		let serialized = serialize_manifest(&entries);
+		let (schema_version, host_triple, parsed) = parse_manifest(&serialized).expect("manifest should parse");
+
+		assert_eq!(schema_version, MANIFEST_SCHEMA_VERSION);
+		assert_eq!(host_triple.as_deref(), HOST_TRIPLE.as_deref());
+		assert_eq!(parsed.len(), entries.len());
+		for (module, fingerprint) in &entries {
+			assert_eq!(parsed.get(module), Some(fingerprint));
+		}
+	}
+
+	#[test]
+	fn manifest_round_trips_with_no_modules() {
+		let entries = HashMap::new();
+		let serialized = serialize_manifest(&entries);
+		let (schema_version, _host_triple, parsed) = parse_manifest(&serialized).expect("manifest should parse");
+		assert_eq!(schema_version, MANIFEST_SCHEMA_VERSION);
+		assert!(parsed.is_empty());
+	}
+
+	#[test]
+	fn parse_manifest_rejects_garbage() {
+		assert!(parse_manifest("not a manifest").is_none());
+	}
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+use super::super::Result;
+use super::copy_indent;
+
+/// Collects top-level Rust items from several files while collapsing same-name duplicates, keeping
+/// the output in a deterministic, name-sorted order regardless of the order files were added in. Several
+/// translation units can independently produce the exact same generated typedef, tuple or `extern "C"`
+/// shim, and concatenating them as-is (as plain `io::copy` would) re-emits the duplicate and makes the
+/// output order depend on filename sort, which isn't stable enough for caching.
+#[derive(Default)]
+pub struct UniqueEntries(BTreeMap<String, String>);
+
+impl UniqueEntries {
+	pub fn new() -> Self {
+		Self(BTreeMap::new())
+	}
+
+	/// Reads `path` and inserts each of its top-level items, keyed by the item's own name (or, for an
+	/// `extern "C"` block, the sorted set of function names it declares) so that items coming from
+	/// different files collapse into a single entry even when they differ in incidental formatting, like a
+	/// stray attribute or comment line ahead of the declaration. Two items sharing a name but disagreeing
+	/// on their (comment/attribute-normalized) body is an error: same-name used to mean identical bodies
+	/// (enforced for free by the duplicate-symbol compile error re-emitting both would have caused), and
+	/// silently keeping whichever file sorts first would turn that into a silently wrong binding instead.
+	pub fn add_file(&mut self, path: &Path) -> Result<()> {
+		let contents = fs::read_to_string(path)?;
+		for item in split_top_level_items(&contents) {
+			if item.trim().is_empty() {
+				continue;
+			}
+			let key = item_key(&item);
+			if let Some(existing) = self.0.get(&key) {
+				if normalize_item(existing) != normalize_item(&item) {
+					return Err(format!(
+						"Generated item `{key}` has conflicting definitions while merging {}: \
+						 \n--- existing ---\n{existing}\n--- new ---\n{item}",
+						path.display()
+					)
+					.into());
+				}
+				continue;
+			}
+			self.0.insert(key, item);
+		}
+		Ok(())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Writes out the deduplicated items in name-sorted order, each line indented by `indent`.
+	pub fn write_indented(&self, mut write: impl Write, indent: &str) -> Result<()> {
+		for item in self.0.values() {
+			copy_indent(BufReader::new(item.as_bytes()), &mut write, indent)?;
+		}
+		Ok(())
+	}
+}
+
+/// Derives the name this item should be deduplicated and sorted by: the declared name for a `type`,
+/// `struct`, `union`, `enum` or `fn` item, or the sorted, deduplicated list of function names declared
+/// inside an `extern "C" { ... }` block. Falls back to the item's own trimmed text for anything else, so an
+/// unrecognized shape still dedupes on exact matches instead of panicking.
+fn item_key(item: &str) -> String {
+	let decl_line = item
+		.lines()
+		.map(str::trim)
+		.find(|line| !line.is_empty() && !line.starts_with("//") && !line.starts_with('#'));
+	if decl_line.map_or(false, |line| line.starts_with("extern \"C\"")) {
+		let mut fn_names = item
+			.lines()
+			.filter_map(|line| {
+				let line = line.trim_start();
+				line.strip_prefix("pub fn ").or_else(|| line.strip_prefix("fn "))
+			})
+			.filter_map(|after_fn| {
+				let name_end = after_fn.find(|c: char| c == '(' || c == '<' || c.is_whitespace())?;
+				Some(&after_fn[..name_end])
+			})
+			.collect::<Vec<_>>();
+		fn_names.sort_unstable();
+		fn_names.dedup();
+		return format!("extern \"C\" fn {}", fn_names.join(", "));
+	}
+	for line in item.lines() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+			continue;
+		}
+		return parse_item_name(line).unwrap_or_else(|| item.trim().to_string());
+	}
+	item.trim().to_string()
+}
+
+/// Normalizes an item's body for conflict comparison by dropping blank lines, comment lines (`//...`) and
+/// attribute lines (`#...`) and trimming the rest, so two items that are the same declaration modulo an
+/// incidental doc comment or attribute compare equal.
+fn normalize_item(item: &str) -> String {
+	item
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with("//") && !line.starts_with('#'))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Parses the declared name out of a `type`/`struct`/`union`/`enum`/`fn` item's first line.
+fn parse_item_name(line: &str) -> Option<String> {
+	for prefix in [
+		"pub type ",
+		"type ",
+		"pub struct ",
+		"struct ",
+		"pub union ",
+		"union ",
+		"pub enum ",
+		"enum ",
+		"pub fn ",
+		"fn ",
+	] {
+		if let Some(rest) = line.strip_prefix(prefix) {
+			let name_end = rest
+				.find(|c: char| c == '(' || c == '<' || c == '{' || c == ';' || c.is_whitespace())
+				.unwrap_or(rest.len());
+			if name_end > 0 {
+				return Some(rest[..name_end].to_string());
+			}
+		}
+	}
+	None
+}
+
+/// Splits generated source into top-level items by tracking brace depth; each item is assumed to end at
+/// the line where depth returns to 0 and the line ends with `;` or `}`, which holds for the generated
+/// typedefs, tuples and `extern "C"` blocks this is used on.
+fn split_top_level_items(src: &str) -> Vec<String> {
+	let mut items = Vec::new();
+	let mut current = String::new();
+	let mut depth = 0i32;
+	for line in src.lines() {
+		if current.is_empty() && line.trim().is_empty() {
+			continue;
+		}
+		current.push_str(line);
+		current.push('\n');
+		depth += line.matches('{').count() as i32;
+		depth -= line.matches('}').count() as i32;
+		let line = line.trim_end();
+		if depth <= 0 && (line.ends_with(';') || line.ends_with('}')) {
+			items.push(std::mem::take(&mut current));
+			depth = 0;
+		}
+	}
+	if !current.trim().is_empty() {
+		items.push(current);
+	}
+	items
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn splits_typedefs_and_extern_blocks() {
+		let src = "\
+pub type Foo = *mut c_void;
+pub struct Bar(*mut c_void);
+
+extern \"C\" {
+	fn foo_new() -> *mut c_void;
+	fn foo_delete(instance: *mut c_void);
+}
+";
+		let items = split_top_level_items(src);
+		assert_eq!(items.len(), 3);
+		assert_eq!(items[0].trim(), "pub type Foo = *mut c_void;");
+		assert_eq!(items[1].trim(), "pub struct Bar(*mut c_void);");
+		assert!(items[2].trim_start().starts_with("extern \"C\" {"));
+		assert!(items[2].contains("fn foo_new()"));
+		assert!(items[2].contains("fn foo_delete(instance: *mut c_void);"));
+	}
+
+	#[test]
+	fn keeps_extern_block_intact_across_nested_extern_fn_types() {
+		// A single extern "C" block containing several functions, one of which takes an `extern "C" fn(...)`
+		// parameter, must stay one item: the brace-depth tracking must not split in the middle of it, and the
+		// following block must become an item of its own.
+		let src = "\
+extern \"C\" {
+	fn first_fn(cb: extern \"C\" fn(*mut c_void)) -> bool;
+	fn second_fn() -> *mut c_void;
+}
+extern \"C\" {
+	fn third_fn();
+}
+";
+		let items = split_top_level_items(src);
+		assert_eq!(items.len(), 2);
+		assert!(items[0].contains("first_fn"));
+		assert!(items[0].contains("second_fn"));
+		assert!(!items[0].contains("third_fn"));
+		assert!(items[1].contains("third_fn"));
+	}
+
+	#[test]
+	fn item_key_collapses_incidental_formatting_differences() {
+		let a = "/// some doc comment\npub type Foo = *mut c_void;\n".to_string();
+		let b = "#[allow(dead_code)]\npub type Foo = *mut c_void;\n".to_string();
+		assert_eq!(item_key(&a), item_key(&b));
+	}
+
+	#[test]
+	fn item_key_for_extern_block_is_its_sorted_function_names() {
+		let item = "extern \"C\" {\n\tfn b_fn();\n\tfn a_fn();\n}\n";
+		assert_eq!(item_key(item), "extern \"C\" fn a_fn, b_fn");
+	}
+
+	#[test]
+	fn normalize_item_ignores_comments_and_attributes() {
+		let a = "/// doc\n#[allow(dead_code)]\npub type Foo = *mut c_void;\n";
+		let b = "pub type Foo = *mut c_void;\n";
+		assert_eq!(normalize_item(a), normalize_item(b));
+	}
+
+	#[test]
+	fn normalize_item_distinguishes_real_body_differences() {
+		let a = "pub type Foo = *mut c_void;\n";
+		let b = "pub type Foo = *const c_void;\n";
+		assert_ne!(normalize_item(a), normalize_item(b));
+	}
+
+	#[test]
+	fn add_file_merges_same_name_items_with_identical_bodies() {
+		let dir = std::env::temp_dir().join(format!("ocvrs-unique-entries-test-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let file_a = dir.join("a.rs");
+		let file_b = dir.join("b.rs");
+		fs::write(&file_a, "/// from a\npub type Foo = *mut c_void;\n").unwrap();
+		fs::write(&file_b, "#[allow(dead_code)]\npub type Foo = *mut c_void;\n").unwrap();
+
+		let mut entries = UniqueEntries::new();
+		entries.add_file(&file_a).unwrap();
+		entries.add_file(&file_b).unwrap();
+		assert_eq!(entries.0.len(), 1);
+
+		let _ = fs::remove_dir_all(&dir);
+	}
+
+	#[test]
+	fn add_file_errors_on_conflicting_bodies_for_the_same_name() {
+		let dir = std::env::temp_dir().join(format!("ocvrs-unique-entries-test-conflict-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let file_a = dir.join("a.rs");
+		let file_b = dir.join("b.rs");
+		fs::write(&file_a, "pub type Foo = *mut c_void;\n").unwrap();
+		fs::write(&file_b, "pub type Foo = *const c_void;\n").unwrap();
+
+		let mut entries = UniqueEntries::new();
+		entries.add_file(&file_a).unwrap();
+		let err = entries.add_file(&file_b).unwrap_err();
+		assert!(err.to_string().contains("Foo"));
+
+		let _ = fs::remove_dir_all(&dir);
+	}
+}
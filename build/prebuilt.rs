@@ -0,0 +1,112 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Library, Result};
+
+/// When set to `1`, skip running the binding generator entirely and reuse the checked-in bindings produced
+/// by a previous [export_prebuilt_bindings] run instead. Also kicks in automatically when libclang can't be
+/// initialized, so sandboxed builds (docs.rs-like environments, cross builds, CI without a clang toolchain)
+/// still succeed.
+const USE_PREBUILT_BINDINGS_ENV: &str = "OCVRS_USE_PREBUILT_BINDINGS";
+
+/// When set, export the bindings generated and collected this run into the version-keyed directory, so they
+/// can be checked in for later `OCVRS_USE_PREBUILT_BINDINGS` builds. Has no effect on a build that itself
+/// consumes the prebuilt bindings.
+const EXPORT_PREBUILT_BINDINGS_ENV: &str = "OCVRS_EXPORT_PREBUILT_BINDINGS";
+
+pub fn use_prebuilt_bindings() -> bool {
+	matches!(env::var(USE_PREBUILT_BINDINGS_ENV).as_deref(), Ok("1"))
+}
+
+pub fn export_requested() -> bool {
+	env::var_os(EXPORT_PREBUILT_BINDINGS_ENV).is_some()
+}
+
+/// Directory holding the checked-in bindings for the OpenCV version `opencv` was detected as, e.g.
+/// `src/bindings/opencv_49` for OpenCV 4.9.x. Mirrors the per-minor-version layout of the old
+/// `get_versioned_hub_dir`.
+pub fn prebuilt_bindings_dir(src_dir: &Path, opencv: &Library) -> PathBuf {
+	src_dir
+		.join("bindings")
+		.join(format!("opencv_{}{}", opencv.version.major, opencv.version.minor))
+}
+
+/// Copies the already-collected `hub.rs`/`types.rs`/`sys.rs`, per-module `{module}.rs`/`{module}.cpp` and
+/// merged `{module}_types.hpp` out of `target_module_dir`/`out_dir` into `prebuilt_dir` so they can be
+/// checked in and later consumed by [collect_prebuilt_bindings] without needing libclang. Parallel to
+/// `transfer_bindings_to_docs`, but the destination is part of the source tree rather than a docs artifact.
+pub fn export_prebuilt_bindings(out_dir: &Path, target_module_dir: &Path, modules: &[String], prebuilt_dir: &Path) -> Result<()> {
+	fs::create_dir_all(prebuilt_dir)?;
+	for file in ["hub.rs", "types.rs", "sys.rs"] {
+		fs::copy(target_module_dir.join(file), prebuilt_dir.join(file))?;
+	}
+	for module in modules {
+		fs::copy(
+			target_module_dir.join(format!("{module}.rs")),
+			prebuilt_dir.join(format!("{module}.rs")),
+		)?;
+		// `{module}.cpp` is the native bridge source `collect_generated_bindings` leaves behind in
+		// `out_dir` for a later `cc`-compile step, it has to round-trip too or a prebuilt-bindings build
+		// would have no native side to compile.
+		fs::copy(
+			out_dir.join(format!("{module}.cpp")),
+			prebuilt_dir.join(format!("{module}.cpp")),
+		)?;
+		let module_types_hpp = out_dir.join(format!("{module}_types.hpp"));
+		if module_types_hpp.is_file() {
+			fs::copy(&module_types_hpp, prebuilt_dir.join(format!("{module}_types.hpp")))?;
+		}
+	}
+	Ok(())
+}
+
+/// Reconstructs the generated bindings in `target_module_dir`/`out_dir` from `prebuilt_dir` instead of from
+/// a fresh `run_binding_generator` pass. The files in `prebuilt_dir` are already fully merged (unlike the
+/// raw per-translation-unit fragments `collect_generated_bindings` works from), so this is a plain copy.
+pub fn collect_prebuilt_bindings(modules: &[String], prebuilt_dir: &Path, target_module_dir: &Path, out_dir: &Path) -> Result<()> {
+	// `prebuilt_dir` is keyed by OpenCV major.minor only, not by the module set that was enabled when it
+	// was exported, so a different module set (no contrib, a different contrib subset, ...) for the same
+	// OpenCV version is an everyday mismatch. Check for it upfront and name what's missing instead of
+	// letting the first `fs::copy` below fail with a confusing "No such file or directory".
+	let missing_modules = modules
+		.iter()
+		.filter(|module| !prebuilt_dir.join(format!("{module}.rs")).is_file())
+		.cloned()
+		.collect::<Vec<_>>();
+	if !missing_modules.is_empty() {
+		return Err(format!(
+			"Prebuilt bindings in {} don't contain the following module(s) enabled by the detected OpenCV \
+			 installation: {}. Re-export the prebuilt bindings for this module set (OCVRS_EXPORT_PREBUILT_BINDINGS=1 \
+			 with a regular, non-prebuilt build) or unset OCVRS_USE_PREBUILT_BINDINGS.",
+			prebuilt_dir.display(),
+			missing_modules.join(", ")
+		)
+		.into());
+	}
+
+	if !target_module_dir.exists() {
+		fs::create_dir(target_module_dir)?;
+	}
+	for file in ["hub.rs", "types.rs", "sys.rs"] {
+		fs::copy(prebuilt_dir.join(file), target_module_dir.join(file))?;
+	}
+	for module in modules {
+		fs::copy(
+			prebuilt_dir.join(format!("{module}.rs")),
+			target_module_dir.join(format!("{module}.rs")),
+		)?;
+		// Restore the native bridge source alongside the bindings: `gen_wrapper` skips
+		// `run_binding_generator` entirely in prebuilt mode, so `{module}.cpp` is otherwise never produced
+		// and the native side would have nothing to compile.
+		fs::copy(
+			prebuilt_dir.join(format!("{module}.cpp")),
+			out_dir.join(format!("{module}.cpp")),
+		)?;
+		let module_types_hpp = prebuilt_dir.join(format!("{module}_types.hpp"));
+		if module_types_hpp.is_file() {
+			fs::copy(&module_types_hpp, out_dir.join(format!("{module}_types.hpp")))?;
+		}
+	}
+	Ok(())
+}